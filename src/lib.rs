@@ -6,9 +6,17 @@ use std::{
     fs,
     io::{self, Write as _},
     path::{Path, PathBuf},
-    sync::{PoisonError, RwLock, RwLockReadGuard},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{sync_channel, Receiver, SyncSender},
+        Arc, PoisonError, RwLock, RwLockReadGuard,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime},
 };
 
+type Clock = Arc<dyn Fn() -> DateTime<FixedOffset> + Send + Sync>;
+
 #[derive(Debug, Clone)]
 pub enum RotationSize {
     Bytes(u64),
@@ -38,6 +46,8 @@ pub enum Compression {
     Snappy,
 }
 
+const COMPRESSED_EXT_PATTERN: &str = r"\.(gz|zst|lz4|bz2|xz|sz)";
+
 #[derive(Debug, Clone)]
 pub enum TimeZone {
     UTC,
@@ -52,44 +62,88 @@ pub enum RotationAge {
     Daily,
 }
 
+impl RotationAge {
+    /// `chrono` strftime pattern used to render a rolled file's period suffix,
+    /// e.g. `app.2024-01-01-10-30` for `Minutely`.
+    fn strftime_pattern(&self) -> &'static str {
+        match self {
+            RotationAge::Minutely => "%Y-%m-%d-%H-%M",
+            RotationAge::Hourly => "%Y-%m-%d-%H",
+            RotationAge::Daily => "%Y-%m-%d",
+        }
+    }
+
+    /// Regex fragment matching the period suffix produced by [`Self::strftime_pattern`].
+    fn date_regex_pattern(&self) -> &'static str {
+        match self {
+            RotationAge::Minutely => r"\d{4}-\d{2}-\d{2}-\d{2}-\d{2}",
+            RotationAge::Hourly => r"\d{4}-\d{2}-\d{2}-\d{2}",
+            RotationAge::Daily => r"\d{4}-\d{2}-\d{2}",
+        }
+    }
+}
+
 #[derive(Clone)]
 pub enum Rotation {
     SizeBased(RotationSize),
     AgeBased(RotationAge),
+    AgeAndSize(RotationAge, RotationSize),
 }
 
 #[derive(Clone)]
 struct LogRollerMeta {
     directory: PathBuf,
     filename: PathBuf,
+    filename_suffix: Option<String>,
     rotation: Rotation,
     time_zone: TimeZone,
     compression: Option<Compression>,
     max_keep_files: Option<u64>,
-    // max_compressed_files: Option<u64>,
+    max_keep_bytes: Option<u64>,
+    max_age: Option<Duration>,
+    clock: Clock,
+    /// When set, rollover post-processing (compression/pruning) runs inline on the
+    /// calling thread instead of being spawned onto a detached one-shot thread.
+    /// Set by [`NonBlockingBuilder::finish`] so the single worker thread stays the
+    /// only thread doing I/O for a given roller.
+    inline_rollover_processing: bool,
 }
 
 struct LogRollerState {
     next_size_based_index: usize,
     next_age_based_time: DateTime<FixedOffset>,
+    next_age_size_sub_index: usize,
 
     curr_file_path: PathBuf,
     curr_file_size_bytes: u64,
 }
 
+fn strip_filename_suffix<'a>(value: &'a str, filename_suffix: Option<&str>) -> Option<&'a str> {
+    match filename_suffix {
+        Some(suffix) => value.strip_suffix(&format!(".{suffix}")),
+        None => Some(value),
+    }
+}
+
 impl LogRollerState {
-    fn get_next_size_based_index(directory: &PathBuf, filename: &Path) -> usize {
+    fn get_next_size_based_index(
+        directory: &PathBuf,
+        filename: &Path,
+        filename_suffix: Option<&str>,
+    ) -> usize {
         let mut max_suffix = 0;
         if directory.is_dir() {
             if let Ok(files) = std::fs::read_dir(directory) {
                 for file in files.flatten() {
                     if let Some(exist_file) = file.file_name().to_str() {
-                        if exist_file.starts_with(&filename.to_string_lossy().to_string()) {
-                            if let Some(suffix_str) =
-                                exist_file.strip_prefix(&format!("{}.", filename.to_string_lossy()))
+                        if let Some(index_str) =
+                            exist_file.strip_prefix(&format!("{}.", filename.to_string_lossy()))
+                        {
+                            if let Some(index_str) =
+                                strip_filename_suffix(index_str, filename_suffix)
                             {
-                                if let Ok(suffix) = suffix_str.parse::<usize>() {
-                                    max_suffix = std::cmp::max(max_suffix, suffix);
+                                if let Ok(index) = index_str.parse::<usize>() {
+                                    max_suffix = std::cmp::max(max_suffix, index);
                                 }
                             }
                         }
@@ -103,6 +157,33 @@ impl LogRollerState {
     fn get_curr_size_based_file_size(log_path: &Path) -> u64 {
         std::fs::metadata(log_path).map_or(0, |m| m.len())
     }
+
+    fn get_next_age_size_sub_index(
+        directory: &Path,
+        filename: &Path,
+        period: &str,
+        filename_suffix: Option<&str>,
+    ) -> usize {
+        let mut max_suffix = 0;
+        let prefix = format!("{}.{period}.", filename.to_string_lossy());
+        if directory.is_dir() {
+            if let Ok(files) = std::fs::read_dir(directory) {
+                for file in files.flatten() {
+                    if let Some(exist_file) = file.file_name().to_str() {
+                        if let Some(suffix_str) = exist_file
+                            .strip_prefix(&prefix)
+                            .and_then(|rest| strip_filename_suffix(rest, filename_suffix))
+                        {
+                            if let Ok(suffix) = suffix_str.parse::<usize>() {
+                                max_suffix = std::cmp::max(max_suffix, suffix);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        max_suffix + 1
+    }
 }
 
 pub struct LogRoller {
@@ -111,28 +192,55 @@ pub struct LogRoller {
     writer: RwLock<fs::File>,
 }
 
+/// Which condition caused [`LogRoller::should_rollover`] to return a path, so
+/// callers don't have to re-derive it from a second, unsynchronized clock read.
+enum RolloverTrigger {
+    Age,
+    Size,
+}
+
 impl LogRoller {
-    fn should_rollover(meta: &LogRollerMeta, state: &LogRollerState) -> Option<PathBuf> {
+    fn should_rollover(
+        meta: &LogRollerMeta,
+        state: &LogRollerState,
+    ) -> Option<(PathBuf, RolloverTrigger)> {
         match &meta.rotation {
             Rotation::SizeBased(rotation_size) => {
                 if state.curr_file_size_bytes >= rotation_size.bytes() {
-                    return Some(
-                        meta.directory.join(PathBuf::from(
-                            format!(
-                                "{}.{}",
-                                meta.filename.as_path().to_string_lossy(),
-                                state.next_size_based_index
-                            )
-                            .to_string(),
-                        )),
-                    );
+                    return Some((
+                        meta.format_name(Some(&state.next_size_based_index.to_string())),
+                        RolloverTrigger::Size,
+                    ));
                 }
             }
             Rotation::AgeBased(rotation_age) => {
                 let now = meta.now();
                 let next_time = state.next_age_based_time;
                 if now >= next_time {
-                    return Some(meta.get_next_age_based_log_path(rotation_age, &next_time));
+                    return Some((
+                        meta.get_next_age_based_log_path(rotation_age, &next_time, None),
+                        RolloverTrigger::Age,
+                    ));
+                }
+            }
+            Rotation::AgeAndSize(rotation_age, rotation_size) => {
+                let now = meta.now();
+                let next_time = state.next_age_based_time;
+                if now >= next_time {
+                    return Some((
+                        meta.get_next_age_based_log_path(rotation_age, &next_time, None),
+                        RolloverTrigger::Age,
+                    ));
+                }
+                if state.curr_file_size_bytes >= rotation_size.bytes() {
+                    return Some((
+                        meta.get_next_age_based_log_path(
+                            rotation_age,
+                            &now,
+                            Some(state.next_age_size_sub_index),
+                        ),
+                        RolloverTrigger::Size,
+                    ));
                 }
             }
         }
@@ -142,12 +250,12 @@ impl LogRoller {
 
 impl LogRollerMeta {
     fn now(&self) -> DateTime<FixedOffset> {
-        let tz = match &self.time_zone {
-            TimeZone::UTC => Utc::now().fixed_offset().offset().to_owned(),
-            TimeZone::Local => Local::now().offset().to_owned(),
-            TimeZone::Fix(offset) => offset.to_owned(),
-        };
-        Local::now().with_timezone(&tz)
+        let instant = (self.clock)();
+        match &self.time_zone {
+            TimeZone::UTC => instant.with_timezone(&Utc).fixed_offset(),
+            TimeZone::Local => instant,
+            TimeZone::Fix(offset) => instant.with_timezone(offset),
+        }
     }
 
     #[allow(deprecated)]
@@ -222,35 +330,52 @@ impl LogRollerMeta {
                 .as_os_str()
                 .to_string_lossy()
                 .as_ref(),
+            meta.filename_suffix.as_deref(),
             &meta.rotation,
             meta.max_keep_files,
+            meta.max_keep_bytes,
+            meta.max_age,
+            SystemTime::from(meta.now()),
         )?;
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn prune(
         directory: &PathBuf,
         filename: &str,
+        filename_suffix: Option<&str>,
         rotation: &Rotation,
         max_keep_files: Option<u64>,
+        max_keep_bytes: Option<u64>,
+        max_age: Option<Duration>,
+        now: SystemTime,
     ) -> Result<(), LogRollerError> {
-        let max_keep_files = match max_keep_files {
-            Some(max_keep_files) => max_keep_files,
-            None => {
-                return Ok(());
-            }
+        if max_keep_files.is_none() && max_keep_bytes.is_none() && max_age.is_none() {
+            return Ok(());
+        }
+        let suffix_pattern = match filename_suffix {
+            Some(suffix) => format!(r"\.{suffix}"),
+            None => String::new(),
         };
         let file_pattern = match rotation {
-            Rotation::SizeBased(_) => Regex::new(&format!(r"^{filename}(\.\d+)?(\.gz)?$"))
-                .map_err(|err| LogRollerError::InternalError(err.to_string()))?,
+            Rotation::SizeBased(_) => Regex::new(&format!(
+                r"^{filename}(\.\d+)?{suffix_pattern}({COMPRESSED_EXT_PATTERN})?$"
+            ))
+            .map_err(|err| LogRollerError::InternalError(err.to_string()))?,
             Rotation::AgeBased(rotation_age) => {
-                let pattern = match rotation_age {
-                    RotationAge::Minutely => r"\d{4}-\d{2}-\d{2}-\d{2}-\d{2}",
-                    RotationAge::Hourly => r"\d{4}-\d{2}-\d{2}-\d{2}",
-                    RotationAge::Daily => r"\d{4}-\d{2}-\d{2}",
-                };
-                Regex::new(&format!(r"^{filename}\.{pattern}(\.gz)?$"))
-                    .map_err(|err| LogRollerError::InternalError(err.to_string()))?
+                let pattern = rotation_age.date_regex_pattern();
+                Regex::new(&format!(
+                    r"^{filename}\.{pattern}{suffix_pattern}({COMPRESSED_EXT_PATTERN})?$"
+                ))
+                .map_err(|err| LogRollerError::InternalError(err.to_string()))?
+            }
+            Rotation::AgeAndSize(rotation_age, _) => {
+                let pattern = rotation_age.date_regex_pattern();
+                Regex::new(&format!(
+                    r"^{filename}\.{pattern}(\.\d+)?{suffix_pattern}({COMPRESSED_EXT_PATTERN})?$"
+                ))
+                .map_err(|err| LogRollerError::InternalError(err.to_string()))?
             }
         };
 
@@ -265,22 +390,48 @@ impl LogRollerMeta {
             }
             if let Some(file_name) = file.file_name().to_str() {
                 if file_pattern.is_match(file_name) {
-                    all_files.push((metadata.created()?, file));
+                    all_files.push((metadata.created()?, metadata.len(), file));
                 }
             }
         }
 
-        if all_files.len() < max_keep_files as usize {
-            return Ok(());
+        all_files.sort_by_key(|(created_at, ..)| created_at.to_owned());
+
+        let mut files_to_remove = Vec::new();
+
+        if let Some(max_age) = max_age {
+            if let Some(cutoff) = now.checked_sub(max_age) {
+                all_files.retain(|(created_at, _, file)| {
+                    if *created_at < cutoff {
+                        files_to_remove.push(file.path());
+                        false
+                    } else {
+                        true
+                    }
+                });
+            }
+        }
+
+        if let Some(max_keep_bytes) = max_keep_bytes {
+            let mut total_bytes: u64 = all_files.iter().map(|(_, size, _)| size).sum();
+            while total_bytes > max_keep_bytes && !all_files.is_empty() {
+                let (_, size, file) = all_files.remove(0);
+                total_bytes -= size;
+                files_to_remove.push(file.path());
+            }
         }
 
-        all_files.sort_by_key(|(created_at, _)| created_at.to_owned());
+        if let Some(max_keep_files) = max_keep_files {
+            if all_files.len() > max_keep_files as usize {
+                let excess = all_files.len() - max_keep_files as usize;
+                for (_, _, file) in all_files.drain(..excess) {
+                    files_to_remove.push(file.path());
+                }
+            }
+        }
 
-        for (_, file) in all_files
-            .iter()
-            .take(all_files.len() - max_keep_files as usize)
-        {
-            if let Err(remove_log_file_err) = fs::remove_file(file.path()) {
+        for path in files_to_remove {
+            if let Err(remove_log_file_err) = fs::remove_file(path) {
                 eprintln!("Couldn't remove log file: {remove_log_file_err:?}");
             }
         }
@@ -314,11 +465,128 @@ impl LogRollerMeta {
 
                 fs::remove_file(log_path).map_err(LogRollerError::FileIOError)?;
             }
-            Compression::Bzip2
-            | Compression::LZ4
-            | Compression::Zstd
-            | Compression::XZ
-            | Compression::Snappy => {}
+            Compression::Zstd => {
+                #[cfg(feature = "zstd")]
+                {
+                    let infile = fs::File::open(log_path).map_err(LogRollerError::FileIOError)?;
+                    let mut reader = io::BufReader::new(infile);
+
+                    let outfile = fs::File::create(PathBuf::from(format!(
+                        "{}.zst",
+                        log_path.to_string_lossy()
+                    )))
+                    .map_err(LogRollerError::FileIOError)?;
+
+                    let mut encoder = zstd::stream::Encoder::new(outfile, 0)
+                        .map_err(LogRollerError::FileIOError)?;
+                    io::copy(&mut reader, &mut encoder)?;
+                    encoder.finish().map_err(LogRollerError::FileIOError)?;
+
+                    fs::remove_file(log_path).map_err(LogRollerError::FileIOError)?;
+                }
+                #[cfg(not(feature = "zstd"))]
+                {
+                    return Err(LogRollerError::CompressionFeatureDisabled("zstd"));
+                }
+            }
+            Compression::LZ4 => {
+                #[cfg(feature = "lz4")]
+                {
+                    let infile = fs::File::open(log_path).map_err(LogRollerError::FileIOError)?;
+                    let mut reader = io::BufReader::new(infile);
+
+                    let outfile = fs::File::create(PathBuf::from(format!(
+                        "{}.lz4",
+                        log_path.to_string_lossy()
+                    )))
+                    .map_err(LogRollerError::FileIOError)?;
+
+                    let mut encoder = lz4_flex::frame::FrameEncoder::new(outfile);
+                    io::copy(&mut reader, &mut encoder)?;
+                    encoder
+                        .finish()
+                        .map_err(|err| LogRollerError::InternalError(err.to_string()))?;
+
+                    fs::remove_file(log_path).map_err(LogRollerError::FileIOError)?;
+                }
+                #[cfg(not(feature = "lz4"))]
+                {
+                    return Err(LogRollerError::CompressionFeatureDisabled("lz4"));
+                }
+            }
+            Compression::Bzip2 => {
+                #[cfg(feature = "bzip2")]
+                {
+                    let infile = fs::File::open(log_path).map_err(LogRollerError::FileIOError)?;
+                    let reader = io::BufReader::new(infile);
+
+                    let outfile = fs::File::create(PathBuf::from(format!(
+                        "{}.bz2",
+                        log_path.to_string_lossy()
+                    )))
+                    .map_err(LogRollerError::FileIOError)?;
+                    let writer = io::BufWriter::new(outfile);
+
+                    let mut encoder =
+                        bzip2::write::BzEncoder::new(writer, bzip2::Compression::default());
+                    io::copy(&mut io::Read::take(reader, u64::MAX), &mut encoder)?;
+                    encoder.finish()?;
+
+                    fs::remove_file(log_path).map_err(LogRollerError::FileIOError)?;
+                }
+                #[cfg(not(feature = "bzip2"))]
+                {
+                    return Err(LogRollerError::CompressionFeatureDisabled("bzip2"));
+                }
+            }
+            Compression::XZ => {
+                #[cfg(feature = "xz")]
+                {
+                    let infile = fs::File::open(log_path).map_err(LogRollerError::FileIOError)?;
+                    let reader = io::BufReader::new(infile);
+
+                    let outfile = fs::File::create(PathBuf::from(format!(
+                        "{}.xz",
+                        log_path.to_string_lossy()
+                    )))
+                    .map_err(LogRollerError::FileIOError)?;
+                    let writer = io::BufWriter::new(outfile);
+
+                    let mut encoder = xz2::write::XzEncoder::new(writer, 6);
+                    io::copy(&mut io::Read::take(reader, u64::MAX), &mut encoder)?;
+                    encoder.finish()?;
+
+                    fs::remove_file(log_path).map_err(LogRollerError::FileIOError)?;
+                }
+                #[cfg(not(feature = "xz"))]
+                {
+                    return Err(LogRollerError::CompressionFeatureDisabled("xz"));
+                }
+            }
+            Compression::Snappy => {
+                #[cfg(feature = "snappy")]
+                {
+                    let infile = fs::File::open(log_path).map_err(LogRollerError::FileIOError)?;
+                    let mut reader = io::BufReader::new(infile);
+
+                    let outfile = fs::File::create(PathBuf::from(format!(
+                        "{}.sz",
+                        log_path.to_string_lossy()
+                    )))
+                    .map_err(LogRollerError::FileIOError)?;
+
+                    let mut encoder = snap::write::FrameEncoder::new(outfile);
+                    io::copy(&mut reader, &mut encoder)?;
+                    encoder.flush()?;
+                    drop(encoder);
+
+                    fs::remove_file(log_path).map_err(LogRollerError::FileIOError)?;
+                }
+                #[cfg(not(feature = "snappy"))]
+                {
+                    return Err(LogRollerError::CompressionFeatureDisabled("snappy"));
+                }
+            }
         }
         Ok(())
     }
@@ -332,7 +600,7 @@ impl LogRollerMeta {
         let meta = self.to_owned();
         match &self.rotation {
             Rotation::SizeBased(_) => {
-                let curr_log_path = self.directory.join(&self.filename);
+                let curr_log_path = self.format_name(None);
                 std::fs::rename(&curr_log_path, &new_log_path)
                     .map_err(|_| LogRollerError::RenameFileError)?;
 
@@ -342,30 +610,20 @@ impl LogRollerMeta {
                             eprintln!("Couldn't flush previous writer: {}", err);
                         }
                         *writer = log_file;
-
-                        std::thread::spawn(move || {
-                            if let Err(err) = Self::process_old_logs(&meta, &new_log_path) {
-                                eprintln!("Couldn't compress log file: {}", err);
-                            }
-                        });
+                        self.run_rollover_processing(meta, new_log_path);
                     }
                     Err(err) => {
                         eprintln!("Couldn't create new log file: {}", err);
                     }
                 }
             }
-            Rotation::AgeBased(_) => match self.create_log_file(&new_log_path) {
+            Rotation::AgeBased(_) | Rotation::AgeAndSize(..) => match self.create_log_file(&new_log_path) {
                 Ok(log_file) => {
                     if let Err(err) = writer.flush() {
                         eprintln!("Couldn't flush previous writer: {}", err);
                     }
                     *writer = log_file;
-
-                    std::thread::spawn(move || {
-                        if let Err(err) = Self::process_old_logs(&meta, &old_log_path) {
-                            eprintln!("Couldn't compress log file: {}", err);
-                        }
-                    });
+                    self.run_rollover_processing(meta, old_log_path);
                 }
                 Err(err) => {
                     eprintln!("Couldn't create new log file: {}", err);
@@ -374,6 +632,26 @@ impl LogRollerMeta {
         }
         Ok(())
     }
+
+    /// Compress/prune the just-rolled-over file at `log_path`. Runs inline on the
+    /// calling thread when `inline_rollover_processing` is set (the non-blocking
+    /// worker thread already owns all I/O for this roller, so there's nothing to
+    /// gain from a detached thread and `WorkerGuard` needs this done before it
+    /// returns); otherwise spawns a one-shot thread, as a synchronous `LogRoller`
+    /// caller shouldn't block on compression/pruning.
+    fn run_rollover_processing(&self, meta: LogRollerMeta, log_path: PathBuf) {
+        if self.inline_rollover_processing {
+            if let Err(err) = Self::process_old_logs(&meta, &log_path) {
+                eprintln!("Couldn't compress log file: {}", err);
+            }
+        } else {
+            std::thread::spawn(move || {
+                if let Err(err) = Self::process_old_logs(&meta, &log_path) {
+                    eprintln!("Couldn't compress log file: {}", err);
+                }
+            });
+        }
+    }
 }
 
 impl LogRollerMeta {
@@ -381,41 +659,65 @@ impl LogRollerMeta {
         LogRollerMeta {
             directory: directory.as_ref().to_path_buf(),
             filename: filename.as_ref().to_path_buf(),
+            filename_suffix: None,
             rotation: Rotation::AgeBased(RotationAge::Daily),
             time_zone: TimeZone::Local,
             compression: None,
             max_keep_files: None,
-            // max_compressed_files: None,
+            max_keep_bytes: None,
+            max_age: None,
+            clock: Arc::new(|| Local::now().fixed_offset()),
+            inline_rollover_processing: false,
         }
     }
 
+    fn format_name(&self, middle: Option<&str>) -> PathBuf {
+        let filename = self.filename.as_path().to_string_lossy();
+        let name = match (middle, &self.filename_suffix) {
+            (Some(middle), Some(suffix)) => format!("{filename}.{middle}.{suffix}"),
+            (Some(middle), None) => format!("{filename}.{middle}"),
+            (None, Some(suffix)) => format!("{filename}.{suffix}"),
+            (None, None) => filename.to_string(),
+        };
+        self.directory.join(PathBuf::from(name))
+    }
+
     fn get_next_age_based_log_path(
         &self,
         rotation_age: &RotationAge,
         datetime: &DateTime<FixedOffset>,
+        sub_index: Option<usize>,
     ) -> PathBuf {
-        let path_fn = |pattern: &str| -> PathBuf {
-            self.directory.join(PathBuf::from(
-                datetime
-                    .format(&format!(
-                        "{}.{pattern}",
-                        self.filename.as_path().to_string_lossy()
-                    ))
-                    .to_string(),
-            ))
-        };
-        match rotation_age {
-            RotationAge::Minutely => path_fn("%Y-%m-%d-%H-%M"),
-            RotationAge::Hourly => path_fn("%Y-%m-%d-%H"),
-            RotationAge::Daily => path_fn("%Y-%m-%d"),
+        let mut middle = datetime.format(rotation_age.strftime_pattern()).to_string();
+        if let Some(sub_index) = sub_index {
+            middle = format!("{middle}.{sub_index}");
         }
+        self.format_name(Some(&middle))
     }
 
     fn get_curr_log_path(&self) -> PathBuf {
         match &self.rotation {
-            Rotation::SizeBased(_) => self.directory.join(self.filename.as_path()),
+            Rotation::SizeBased(_) => self.format_name(None),
             Rotation::AgeBased(rotation_age) => {
-                self.get_next_age_based_log_path(rotation_age, &self.now())
+                self.get_next_age_based_log_path(rotation_age, &self.now(), None)
+            }
+            Rotation::AgeAndSize(rotation_age, _) => {
+                let now = self.now();
+                let period = now.format(rotation_age.strftime_pattern()).to_string();
+                // `get_next_age_size_sub_index` returns the slot the *next* size-triggered
+                // rollover would use, so the last slot actually written (if any) is one below it.
+                let last_sub_index = LogRollerState::get_next_age_size_sub_index(
+                    &self.directory,
+                    &self.filename,
+                    &period,
+                    self.filename_suffix.as_deref(),
+                ) - 1;
+                let sub_index = if last_sub_index == 0 {
+                    None
+                } else {
+                    Some(last_sub_index)
+                };
+                self.get_next_age_based_log_path(rotation_age, &now, sub_index)
             }
         }
     }
@@ -441,6 +743,8 @@ pub enum LogRollerError {
     ShouldNotRotate,
     #[error("Internal error: {0}")]
     InternalError(String),
+    #[error("Compression backend `{0}` was selected but its Cargo feature is not enabled")]
+    CompressionFeatureDisabled(&'static str),
 }
 
 pub struct LogRollerBuilder {
@@ -472,6 +776,15 @@ impl LogRollerBuilder {
         }
     }
 
+    pub fn filename_suffix(self, filename_suffix: impl Into<String>) -> Self {
+        Self {
+            meta: LogRollerMeta {
+                filename_suffix: Some(filename_suffix.into()),
+                ..self.meta
+            },
+        }
+    }
+
     pub fn compression(self, compression: Compression) -> Self {
         Self {
             meta: LogRollerMeta {
@@ -490,6 +803,36 @@ impl LogRollerBuilder {
         }
     }
 
+    pub fn max_keep_bytes(self, max_keep_bytes: u64) -> Self {
+        Self {
+            meta: LogRollerMeta {
+                max_keep_bytes: Some(max_keep_bytes),
+                ..self.meta
+            },
+        }
+    }
+
+    pub fn max_age(self, max_age: Duration) -> Self {
+        Self {
+            meta: LogRollerMeta {
+                max_age: Some(max_age),
+                ..self.meta
+            },
+        }
+    }
+
+    pub fn clock<F>(self, clock: F) -> Self
+    where
+        F: Fn() -> DateTime<FixedOffset> + Send + Sync + 'static,
+    {
+        Self {
+            meta: LogRollerMeta {
+                clock: Arc::new(clock),
+                ..self.meta
+            },
+        }
+    }
+
     pub fn build(self) -> Result<LogRoller, LogRollerError> {
         let curr_file_path = self.meta.get_curr_log_path();
         Ok(LogRoller {
@@ -498,17 +841,36 @@ impl LogRollerBuilder {
                 next_size_based_index: LogRollerState::get_next_size_based_index(
                     &self.meta.directory,
                     &self.meta.filename,
+                    self.meta.filename_suffix.as_deref(),
                 ),
                 next_age_based_time: self.meta.next_time(
                     self.meta.now(),
                     match &self.meta.rotation {
-                        Rotation::AgeBased(rotation_age) => rotation_age.to_owned(),
+                        Rotation::AgeBased(rotation_age) | Rotation::AgeAndSize(rotation_age, _) => {
+                            rotation_age.to_owned()
+                        }
                         _ => RotationAge::Daily,
                     },
                 )?,
+                next_age_size_sub_index: match &self.meta.rotation {
+                    Rotation::AgeAndSize(rotation_age, _) => {
+                        let period = self
+                            .meta
+                            .now()
+                            .format(rotation_age.strftime_pattern())
+                            .to_string();
+                        LogRollerState::get_next_age_size_sub_index(
+                            &self.meta.directory,
+                            &self.meta.filename,
+                            &period,
+                            self.meta.filename_suffix.as_deref(),
+                        )
+                    }
+                    _ => 1,
+                },
                 curr_file_path: curr_file_path.to_owned(),
                 curr_file_size_bytes: LogRollerState::get_curr_size_based_file_size(
-                    &self.meta.directory.join(&self.meta.filename),
+                    &curr_file_path,
                 ),
             },
             writer: RwLock::new(self.meta.create_log_file(&curr_file_path)?),
@@ -524,7 +886,7 @@ impl io::Write for LogRoller {
             .unwrap_or_else(PoisonError::into_inner);
 
         let old_log_path = self.state.curr_file_path.to_owned();
-        if let Some(new_log_path) = Self::should_rollover(&self.meta, &self.state) {
+        if let Some((new_log_path, trigger)) = Self::should_rollover(&self.meta, &self.state) {
             self.meta
                 .refresh_writer(writer, old_log_path, new_log_path.to_owned())
                 .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string()))?;
@@ -542,6 +904,23 @@ impl io::Write for LogRoller {
                         .next_time(self.meta.now(), rotation_age.to_owned())
                         .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string()))?;
                 }
+                Rotation::AgeAndSize(rotation_age, _) => {
+                    self.state.curr_file_size_bytes = 0;
+                    match trigger {
+                        RolloverTrigger::Age => {
+                            self.state.next_age_based_time = self
+                                .meta
+                                .next_time(self.meta.now(), rotation_age.to_owned())
+                                .map_err(|err| {
+                                    io::Error::new(io::ErrorKind::Other, err.to_string())
+                                })?;
+                            self.state.next_age_size_sub_index = 1;
+                        }
+                        RolloverTrigger::Size => {
+                            self.state.next_age_size_sub_index += 1;
+                        }
+                    }
+                }
             }
         }
         self.state.curr_file_size_bytes += buf.len() as u64;
@@ -562,7 +941,7 @@ impl<'a> tracing_subscriber::fmt::writer::MakeWriter<'a> for LogRoller {
 
     fn make_writer(&'a self) -> Self::Writer {
         let old_log_path = self.state.curr_file_path.to_owned();
-        if let Some(new_log_path) = Self::should_rollover(&self.meta, &self.state) {
+        if let Some((new_log_path, _trigger)) = Self::should_rollover(&self.meta, &self.state) {
             if let Err(refresh_writer_err) = self
                 .meta
                 .refresh_writer(
@@ -591,3 +970,328 @@ impl io::Write for RollingWriter<'_> {
         (&*self.0).flush()
     }
 }
+
+const DEFAULT_BUFFERED_LINES_LIMIT: usize = 128_000;
+
+enum Msg {
+    Data(Vec<u8>),
+    Shutdown,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum OverflowPolicy {
+    Block,
+    DropAndCount,
+}
+
+#[derive(Clone)]
+pub struct NonBlocking {
+    sender: SyncSender<Msg>,
+    overflow_policy: OverflowPolicy,
+    dropped_lines: Arc<AtomicU64>,
+}
+
+impl NonBlocking {
+    pub fn dropped_lines(&self) -> u64 {
+        self.dropped_lines.load(Ordering::Acquire)
+    }
+}
+
+impl io::Write for NonBlocking {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.overflow_policy {
+            OverflowPolicy::Block => {
+                self.sender
+                    .send(Msg::Data(buf.to_vec()))
+                    .map_err(|err| io::Error::other(err.to_string()))?;
+            }
+            OverflowPolicy::DropAndCount => {
+                if self.sender.try_send(Msg::Data(buf.to_vec())).is_err() {
+                    self.dropped_lines.fetch_add(1, Ordering::Release);
+                }
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl<'a> tracing_subscriber::fmt::writer::MakeWriter<'a> for NonBlocking {
+    type Writer = NonBlocking;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+pub struct WorkerGuard {
+    sender: SyncSender<Msg>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Drop for WorkerGuard {
+    fn drop(&mut self) {
+        if self.sender.send(Msg::Shutdown).is_ok() {
+            if let Some(handle) = self.handle.take() {
+                if handle.join().is_err() {
+                    eprintln!("Couldn't join non-blocking writer thread");
+                }
+            }
+        }
+    }
+}
+
+fn worker_loop(mut roller: LogRoller, receiver: Receiver<Msg>) {
+    for msg in receiver.iter() {
+        match msg {
+            Msg::Data(buf) => {
+                if let Err(err) = roller.write_all(&buf) {
+                    eprintln!("Couldn't write log record: {err}");
+                }
+            }
+            Msg::Shutdown => break,
+        }
+    }
+    if let Err(err) = roller.flush() {
+        eprintln!("Couldn't flush log writer: {err}");
+    }
+}
+
+pub struct NonBlockingBuilder {
+    buffered_lines_limit: usize,
+    overflow_policy: OverflowPolicy,
+}
+
+impl NonBlockingBuilder {
+    pub fn buffered_lines_limit(self, buffered_lines_limit: usize) -> Self {
+        Self {
+            buffered_lines_limit,
+            ..self
+        }
+    }
+
+    pub fn overflow_policy(self, overflow_policy: OverflowPolicy) -> Self {
+        Self {
+            overflow_policy,
+            ..self
+        }
+    }
+
+    pub fn finish(self, mut roller: LogRoller) -> (NonBlocking, WorkerGuard) {
+        roller.meta.inline_rollover_processing = true;
+        let (sender, receiver) = sync_channel(self.buffered_lines_limit);
+        let worker_sender = sender.clone();
+        let handle = thread::Builder::new()
+            .name("logroller-non-blocking".to_string())
+            .spawn(move || worker_loop(roller, receiver))
+            .expect("Couldn't spawn non-blocking writer thread");
+
+        (
+            NonBlocking {
+                sender,
+                overflow_policy: self.overflow_policy,
+                dropped_lines: Arc::new(AtomicU64::new(0)),
+            },
+            WorkerGuard {
+                sender: worker_sender,
+                handle: Some(handle),
+            },
+        )
+    }
+}
+
+impl Default for NonBlockingBuilder {
+    fn default() -> Self {
+        Self {
+            buffered_lines_limit: DEFAULT_BUFFERED_LINES_LIMIT,
+            overflow_policy: OverflowPolicy::Block,
+        }
+    }
+}
+
+pub fn non_blocking(roller: LogRoller) -> (NonBlocking, WorkerGuard) {
+    NonBlockingBuilder::default().finish(roller)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone as _;
+
+    fn meta_with_clock(now: DateTime<FixedOffset>) -> LogRollerMeta {
+        let mut meta = LogRollerMeta::new("test_dir", "test.log");
+        meta.clock = Arc::new(move || now);
+        meta
+    }
+
+    #[test]
+    fn next_time_minutely_advances_to_next_minute_boundary() {
+        let offset = FixedOffset::east_opt(0).unwrap();
+        let now = offset.with_ymd_and_hms(2024, 1, 1, 10, 15, 42).unwrap();
+        let meta = meta_with_clock(now);
+
+        let next = meta.next_time(now, RotationAge::Minutely).unwrap();
+
+        assert_eq!(next, offset.with_ymd_and_hms(2024, 1, 1, 10, 16, 0).unwrap());
+    }
+
+    #[test]
+    fn next_time_hourly_advances_to_next_hour_boundary() {
+        let offset = FixedOffset::east_opt(0).unwrap();
+        let now = offset.with_ymd_and_hms(2024, 1, 1, 10, 59, 59).unwrap();
+        let meta = meta_with_clock(now);
+
+        let next = meta.next_time(now, RotationAge::Hourly).unwrap();
+
+        assert_eq!(next, offset.with_ymd_and_hms(2024, 1, 1, 11, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn next_time_daily_advances_to_next_midnight() {
+        let offset = FixedOffset::east_opt(0).unwrap();
+        let now = offset.with_ymd_and_hms(2024, 1, 1, 23, 59, 59).unwrap();
+        let meta = meta_with_clock(now);
+
+        let next = meta.next_time(now, RotationAge::Daily).unwrap();
+
+        assert_eq!(next, offset.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn next_time_daily_carries_the_offset_across_a_dst_transition() {
+        // The injected clock is the only source of "now", so a DST transition just
+        // shows up as the offset changing between calls; next_time must carry
+        // whichever offset it was given through to the computed boundary.
+        let before_dst = FixedOffset::east_opt(3600)
+            .unwrap()
+            .with_ymd_and_hms(2024, 3, 30, 23, 30, 0)
+            .unwrap();
+        let meta = meta_with_clock(before_dst);
+        let next = meta.next_time(before_dst, RotationAge::Daily).unwrap();
+        assert_eq!(next.offset(), before_dst.offset());
+        assert_eq!(
+            next,
+            FixedOffset::east_opt(3600)
+                .unwrap()
+                .with_ymd_and_hms(2024, 3, 31, 0, 0, 0)
+                .unwrap()
+        );
+
+        let after_dst = FixedOffset::east_opt(7200)
+            .unwrap()
+            .with_ymd_and_hms(2024, 3, 31, 1, 15, 0)
+            .unwrap();
+        let meta = meta_with_clock(after_dst);
+        let next = meta.next_time(after_dst, RotationAge::Daily).unwrap();
+        assert_eq!(next.offset(), after_dst.offset());
+        assert_eq!(
+            next,
+            FixedOffset::east_opt(7200)
+                .unwrap()
+                .with_ymd_and_hms(2024, 4, 1, 0, 0, 0)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn now_routes_through_the_injected_clock_for_every_time_zone_mode() {
+        let now = FixedOffset::east_opt(3600)
+            .unwrap()
+            .with_ymd_and_hms(2024, 6, 1, 12, 0, 0)
+            .unwrap();
+        let mut meta = meta_with_clock(now);
+
+        meta.time_zone = TimeZone::Local;
+        assert_eq!(meta.now(), now);
+
+        meta.time_zone = TimeZone::UTC;
+        assert_eq!(meta.now(), now.with_timezone(&Utc).fixed_offset());
+
+        let fixed = FixedOffset::east_opt(7200).unwrap();
+        meta.time_zone = TimeZone::Fix(fixed);
+        assert_eq!(meta.now(), now.with_timezone(&fixed));
+    }
+
+    fn unique_test_dir(name: &str) -> PathBuf {
+        use std::sync::atomic::{AtomicU64, Ordering};
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+        std::env::temp_dir().join(format!("logroller-test-{name}-{}-{id}", std::process::id()))
+    }
+
+    #[test]
+    fn age_and_size_rebuild_mid_period_resumes_from_the_last_sub_rotated_file() {
+        let dir = unique_test_dir("age-and-size-restart");
+        fs::create_dir_all(&dir).unwrap();
+
+        let now = FixedOffset::east_opt(0)
+            .unwrap()
+            .with_ymd_and_hms(2024, 1, 1, 10, 0, 0)
+            .unwrap();
+
+        let mut roller = LogRollerBuilder::new(dir.as_path(), Path::new("app"))
+            .rotation(Rotation::AgeAndSize(
+                RotationAge::Daily,
+                RotationSize::Bytes(5),
+            ))
+            .clock(move || now)
+            .build()
+            .unwrap();
+
+        // First write trips past the size threshold but the rollover itself only
+        // fires on the *next* write; the second write lands in the sub-rotated file.
+        roller.write_all(b"123456").unwrap();
+        roller.write_all(b"x").unwrap();
+        drop(roller);
+
+        assert!(dir.join("app.2024-01-01").exists());
+        assert!(dir.join("app.2024-01-01.1").exists());
+
+        // Simulate a restart mid-period: a fresh builder against the same directory
+        // and clock must resume from the last sub-rotated file, not the stale bare one.
+        let rebuilt = LogRollerBuilder::new(dir.as_path(), Path::new("app"))
+            .rotation(Rotation::AgeAndSize(
+                RotationAge::Daily,
+                RotationSize::Bytes(5),
+            ))
+            .clock(move || now)
+            .build()
+            .unwrap();
+
+        assert_eq!(rebuilt.state.curr_file_path, dir.join("app.2024-01-01.1"));
+        assert_eq!(rebuilt.state.curr_file_size_bytes, 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn non_blocking_guard_drop_waits_for_inline_rollover_compression() {
+        let dir = unique_test_dir("non-blocking-compress");
+        fs::create_dir_all(&dir).unwrap();
+
+        let roller = LogRollerBuilder::new(dir.as_path(), Path::new("app"))
+            .rotation(Rotation::SizeBased(RotationSize::Bytes(5)))
+            .compression(Compression::Gzip)
+            .build()
+            .unwrap();
+
+        let (mut writer, guard) = non_blocking(roller);
+        writer.write_all(b"123456").unwrap();
+        writer.write_all(b"x").unwrap();
+        drop(writer);
+        // Dropping the guard joins the single worker thread. With rollover
+        // processing running inline on that thread, the compressed file must
+        // already be on disk the moment drop returns — no detached thread left
+        // to race against.
+        drop(guard);
+
+        assert!(dir.join("app.1.gz").exists());
+        assert!(!dir.join("app.1").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}